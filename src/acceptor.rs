@@ -0,0 +1,367 @@
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use hyper::client::connect::{Connected, Connection};
+use hyper::server::accept::Accept;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite};
+
+use crate::stream::MaybeHttpsStream;
+#[cfg(not(any(feature = "rustls", feature = "openssl")))]
+use crate::stream::{Handshaking, SyncStream};
+#[cfg(feature = "openssl")]
+use crate::stream::{Handshaking, SyncStream};
+use crate::TlsStream;
+
+/// Accepts inbound TLS connections, built from a certificate and private key.
+///
+/// This is the server-side counterpart to [`HttpsConnector`](crate::HttpsConnector):
+/// where the connector dials out and performs a client handshake, `TlsAcceptor`
+/// performs the server handshake on connections that have already been accepted (e.g.
+/// from a `TcpListener`).
+#[cfg(not(any(feature = "rustls", feature = "openssl")))]
+#[derive(Clone)]
+pub struct TlsAcceptor {
+    acceptor: native_tls::TlsAcceptor,
+}
+
+#[cfg(not(any(feature = "rustls", feature = "openssl")))]
+impl TlsAcceptor {
+    /// Build an acceptor from a PKCS #12 identity bundling a certificate and key.
+    pub fn new(identity: native_tls::Identity) -> Result<Self, native_tls::Error> {
+        native_tls::TlsAcceptor::new(identity).map(|acceptor| TlsAcceptor { acceptor })
+    }
+
+    /// Perform the server-side TLS handshake on an already-accepted connection.
+    ///
+    /// This reuses the same `SyncStream`/`Handshaking` bridge the connector uses to
+    /// drive `native_tls`'s synchronous handshake from an async context.
+    pub async fn accept<T>(&self, stream: T) -> io::Result<TlsStream<T>>
+    where
+        T: AsyncRead + AsyncWrite + Unpin,
+    {
+        let acceptor = self.acceptor.clone();
+        let stream = SyncStream { inner: stream };
+        Handshaking {
+            inner: Some(acceptor.accept(stream)),
+        }
+        .await
+        .map(TlsStream::new)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+    }
+}
+
+#[cfg(not(any(feature = "rustls", feature = "openssl")))]
+impl From<native_tls::TlsAcceptor> for TlsAcceptor {
+    fn from(acceptor: native_tls::TlsAcceptor) -> Self {
+        TlsAcceptor { acceptor }
+    }
+}
+
+#[cfg(not(any(feature = "rustls", feature = "openssl")))]
+impl fmt::Debug for TlsAcceptor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("TlsAcceptor")
+    }
+}
+
+/// Accepts inbound TLS connections, built from an OpenSSL acceptor context.
+#[cfg(feature = "openssl")]
+#[derive(Clone)]
+pub struct TlsAcceptor {
+    acceptor: openssl::ssl::SslAcceptor,
+}
+
+#[cfg(feature = "openssl")]
+impl TlsAcceptor {
+    /// Perform the server-side TLS handshake on an already-accepted connection.
+    ///
+    /// This reuses the same `SyncStream`/`Handshaking` bridge the connector uses to
+    /// drive OpenSSL's synchronous handshake from an async context.
+    pub async fn accept<T>(&self, stream: T) -> io::Result<TlsStream<T>>
+    where
+        T: AsyncRead + AsyncWrite + Unpin,
+    {
+        let acceptor = self.acceptor.clone();
+        let stream = SyncStream { inner: stream };
+        Handshaking {
+            inner: Some(acceptor.accept(stream)),
+        }
+        .await
+        .map(TlsStream::new)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl From<openssl::ssl::SslAcceptor> for TlsAcceptor {
+    fn from(acceptor: openssl::ssl::SslAcceptor) -> Self {
+        TlsAcceptor { acceptor }
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl fmt::Debug for TlsAcceptor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("TlsAcceptor")
+    }
+}
+
+/// Accepts inbound TLS connections, built from a rustls server configuration.
+#[cfg(feature = "rustls")]
+#[derive(Clone)]
+pub struct TlsAcceptor {
+    acceptor: tokio_rustls::TlsAcceptor,
+}
+
+#[cfg(feature = "rustls")]
+impl TlsAcceptor {
+    /// Build an acceptor from a rustls server configuration.
+    pub fn new(config: std::sync::Arc<tokio_rustls::rustls::ServerConfig>) -> Self {
+        TlsAcceptor {
+            acceptor: tokio_rustls::TlsAcceptor::from(config),
+        }
+    }
+
+    /// Perform the server-side TLS handshake on an already-accepted connection.
+    pub async fn accept<T>(&self, stream: T) -> io::Result<TlsStream<T>>
+    where
+        T: AsyncRead + AsyncWrite + Unpin,
+    {
+        self.acceptor
+            .accept(stream)
+            .await
+            .map(tokio_rustls::TlsStream::Server)
+    }
+}
+
+#[cfg(feature = "rustls")]
+impl fmt::Debug for TlsAcceptor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("TlsAcceptor")
+    }
+}
+
+/// Wraps an inbound connection stream (e.g. a `TcpListener`'s incoming connections) so
+/// that every accepted connection is upgraded to TLS before being handed to a hyper
+/// server.
+///
+/// Each connection's handshake is driven to completion before that connection is
+/// yielded from `poll_accept`. Handshakes run one at a time: while one is in progress
+/// no further connection is accepted, so a slow or stalled client holds up the
+/// listener until its handshake completes or fails.
+pub struct HttpsAcceptor<A: Accept> {
+    acceptor: TlsAcceptor,
+    incoming: A,
+    lazy: bool,
+    handshake:
+        Option<Pin<Box<dyn Future<Output = io::Result<MaybeHttpsStream<PeekedStream<A::Conn>>>> + Send>>>,
+}
+
+impl<A: Accept> HttpsAcceptor<A> {
+    /// Wrap `incoming` so every accepted connection is upgraded to TLS using `acceptor`.
+    pub fn new(acceptor: TlsAcceptor, incoming: A) -> Self {
+        HttpsAcceptor {
+            acceptor,
+            incoming,
+            lazy: false,
+            handshake: None,
+        }
+    }
+
+    /// Peek at the first byte of each accepted connection before starting the TLS
+    /// handshake, so a client that isn't speaking TLS at all (a port scanner, a
+    /// plaintext HTTP request sent to the wrong port) is dropped immediately instead of
+    /// being handed to the TLS library blind. As with any failed handshake, the
+    /// connection is discarded and the error is not surfaced from `poll_accept`.
+    ///
+    /// Disabled by default: the handshake starts on the raw connection as soon as it's
+    /// accepted.
+    pub fn set_lazy_handshake(&mut self, enabled: bool) {
+        self.lazy = enabled;
+    }
+}
+
+impl<A: Accept> fmt::Debug for HttpsAcceptor<A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("HttpsAcceptor")
+    }
+}
+
+impl<A> Accept for HttpsAcceptor<A>
+where
+    A: Accept + Unpin,
+    A::Conn: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    A::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    type Conn = MaybeHttpsStream<PeekedStream<A::Conn>>;
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    fn poll_accept(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        loop {
+            if let Some(handshake) = self.handshake.as_mut() {
+                match handshake.as_mut().poll(cx) {
+                    Poll::Ready(Ok(conn)) => {
+                        self.handshake = None;
+                        return Poll::Ready(Some(Ok(conn)));
+                    }
+                    Poll::Ready(Err(_)) => {
+                        // A single connection failing its TLS handshake (a reset mid-handshake,
+                        // a port scan, a client that never speaks TLS at all) must not take
+                        // down the listener for every other connection; drop it and accept the
+                        // next one instead of propagating the error out of `poll_accept`.
+                        self.handshake = None;
+                        continue;
+                    }
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+
+            match Pin::new(&mut self.incoming).poll_accept(cx) {
+                Poll::Ready(Some(Ok(conn))) => {
+                    let acceptor = self.acceptor.clone();
+                    let lazy = self.lazy;
+                    self.handshake = Some(Box::pin(async move {
+                        let conn = if lazy {
+                            PeekedStream::detect_tls(conn).await?
+                        } else {
+                            PeekedStream::new(conn)
+                        };
+                        acceptor.accept(conn).await.map(MaybeHttpsStream::Https)
+                    }));
+                }
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e.into()))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Wraps a stream, replaying some already-consumed bytes to readers before reading any
+/// more from the underlying connection.
+///
+/// Used by [`HttpsAcceptor`] to implement [`HttpsAcceptor::set_lazy_handshake`] (the
+/// peeked first byte is replayed so the TLS handshake still sees the whole byte
+/// stream), and by [`HttpsConnector`](crate::HttpsConnector)'s forward-proxy support to
+/// replay any bytes read past a proxy's `CONNECT` response before the real TLS
+/// handshake with the target begins.
+pub struct PeekedStream<T> {
+    leading: std::collections::VecDeque<u8>,
+    inner: T,
+}
+
+impl<T> PeekedStream<T> {
+    fn new(inner: T) -> Self {
+        PeekedStream {
+            leading: std::collections::VecDeque::new(),
+            inner,
+        }
+    }
+
+    /// Wrap `inner`, replaying `leading` to readers before any bytes from `inner`.
+    pub(crate) fn with_leading(inner: T, leading: Vec<u8>) -> Self {
+        PeekedStream {
+            leading: leading.into(),
+            inner,
+        }
+    }
+
+    /// Get a reference to the underlying connection, e.g. to read `peer_addr()` for
+    /// logging.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying connection.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Consume this wrapper, returning the underlying connection.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: AsyncRead + Unpin> PeekedStream<T> {
+    /// Reads the connection's first byte and checks it's a TLS handshake record
+    /// (`0x16`), bailing out early for anything else instead of handing the bytes to
+    /// the TLS library.
+    async fn detect_tls(mut inner: T) -> io::Result<Self> {
+        let mut byte = [0u8; 1];
+        let n = inner.read(&mut byte).await?;
+        if n == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "connection closed before sending any bytes",
+            ));
+        }
+        if byte[0] != 0x16 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a TLS handshake: first byte was not a TLS record type",
+            ));
+        }
+        Ok(PeekedStream::with_leading(inner, vec![byte[0]]))
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for PeekedStream<T> {
+    #[inline]
+    unsafe fn prepare_uninitialized_buffer(&self, buf: &mut [u8]) -> bool {
+        self.inner.prepare_uninitialized_buffer(buf)
+    }
+
+    #[inline]
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.get_mut();
+        if !this.leading.is_empty() {
+            let n = buf.len().min(this.leading.len());
+            for slot in buf.iter_mut().take(n) {
+                *slot = this.leading.pop_front().expect("checked non-empty above");
+            }
+            return Poll::Ready(Ok(n));
+        }
+        Pin::new(&mut this.inner).poll_read(cx, buf)
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for PeekedStream<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+impl<T: Connection> Connection for PeekedStream<T> {
+    fn connected(&self) -> Connected {
+        self.inner.connected()
+    }
+}
+
+impl<T> fmt::Debug for PeekedStream<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("PeekedStream")
+    }
+}
@@ -1,24 +1,135 @@
+use std::any::Any;
 use std::convert::TryFrom;
 use std::fmt;
 use std::future::Future;
+use std::io;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Duration;
 
 use hyper::{client::connect::HttpConnector, service::Service, Uri};
-use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
+use crate::acceptor::PeekedStream;
 use crate::stream::MaybeHttpsStream;
 use crate::TlsConnector;
 
 type BoxError = Box<dyn std::error::Error + Send + Sync>;
 
+/// The error type returned by [`HttpsConnector`]'s [`Service`] implementation.
+pub type Error = BoxError;
+
+/// Exposes the socket-level tuning knobs `HttpsConnector` applies to a freshly dialed
+/// transport, before the TLS handshake begins.
+///
+/// Implemented for `tokio::net::TcpStream`, which is what hyper's `HttpConnector`
+/// yields. `HttpsConnector<T>` detects at runtime (via [`Any`]) whether a dialed
+/// connection is a `TcpStream` and applies [`HttpsConnector::set_nodelay`]/
+/// [`HttpsConnector::set_keepalive`] through this trait when it is; this keeps the
+/// tuning knobs from forcing a `TcpOptions` bound onto every inner connector, including
+/// ones (Unix sockets, mock connectors, ...) that don't dial TCP at all.
+///
+/// Not part of the public API: the public contract is purely the `Any`-based
+/// downcast in [`apply_tcp_options`], so there's nothing for a downstream crate to
+/// implement this against.
+pub(crate) trait TcpOptions {
+    /// Enable or disable `TCP_NODELAY`, i.e. Nagle's algorithm.
+    fn set_nodelay(&self, enabled: bool) -> io::Result<()>;
+
+    /// Enable TCP keepalive with the given idle time, or disable it with `None`.
+    fn set_keepalive(&self, keepalive: Option<Duration>) -> io::Result<()>;
+}
+
+impl TcpOptions for tokio::net::TcpStream {
+    fn set_nodelay(&self, enabled: bool) -> io::Result<()> {
+        tokio::net::TcpStream::set_nodelay(self, enabled)
+    }
+
+    fn set_keepalive(&self, keepalive: Option<Duration>) -> io::Result<()> {
+        let sock_ref = socket2::SockRef::from(self);
+        match keepalive {
+            Some(idle) => sock_ref.set_tcp_keepalive(&socket2::TcpKeepalive::new().with_time(idle)),
+            None => sock_ref.set_keepalive(false),
+        }
+    }
+}
+
+/// Applies the configured nodelay/keepalive settings if `stream` is a
+/// `tokio::net::TcpStream`, detected at runtime via [`Any`] so that `HttpsConnector<T>`
+/// doesn't need a `TcpOptions` bound on every inner connector's `Response` type. A no-op
+/// for any other transport.
+fn apply_tcp_options<S: 'static>(
+    stream: &mut S,
+    nodelay: bool,
+    keepalive: Option<Duration>,
+) -> io::Result<()> {
+    if !nodelay && keepalive.is_none() {
+        return Ok(());
+    }
+    if let Some(tcp) = (stream as &mut dyn Any).downcast_mut::<tokio::net::TcpStream>() {
+        if nodelay {
+            TcpOptions::set_nodelay(tcp, true)?;
+        }
+        if keepalive.is_some() {
+            TcpOptions::set_keepalive(tcp, keepalive)?;
+        }
+    }
+    Ok(())
+}
+
 /// A Connector for the `https` scheme.
 #[derive(Clone)]
 pub struct HttpsConnector<T> {
     force_https: bool,
     http: T,
     tls: TlsConnector,
+    uses_default_tls: bool,
+    alpn_protocols: Vec<String>,
+    server_name: Option<String>,
+    proxy: Option<ProxyConfig>,
+    nodelay: bool,
+    keepalive: Option<Duration>,
+}
+
+/// Configuration for tunneling connections through a forward HTTP proxy.
+///
+/// For `https` targets, the connector dials the proxy and issues an HTTP/1.1
+/// `CONNECT` request before starting the TLS handshake with the real target. For
+/// `http` targets, the connector dials the proxy directly and lets the absolute-form
+/// request pass through unmodified.
+#[derive(Clone, Debug)]
+pub struct ProxyConfig {
+    proxy: Uri,
+    authorization: Option<String>,
+}
+
+impl ProxyConfig {
+    /// Create a new proxy configuration that tunnels through `proxy`.
+    pub fn new(proxy: Uri) -> Self {
+        ProxyConfig {
+            proxy,
+            authorization: None,
+        }
+    }
+
+    /// Set the credentials sent in the `Proxy-Authorization` header of the `CONNECT`
+    /// request (e.g. `"Basic <base64 user:pass>"`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `value` contains a CR or LF byte. The value is written directly into
+    /// the raw `CONNECT` request text, so a caller building it from a computed or
+    /// untrusted value (rather than a hardcoded literal) could otherwise inject extra
+    /// header lines or split the request.
+    pub fn set_authorization(&mut self, value: impl Into<String>) {
+        let value = value.into();
+        assert!(
+            !value.bytes().any(|b| b == b'\r' || b == b'\n'),
+            "proxy authorization value must not contain CR or LF"
+        );
+        self.authorization = Some(value);
+    }
 }
 
 impl HttpsConnector<HttpConnector> {
@@ -42,24 +153,58 @@ impl HttpsConnector<HttpConnector> {
     /// To handle that error yourself, you can use the `HttpsConnector::from`
     /// constructor after trying to make a `TlsConnector`.
     pub fn new() -> Self {
-        Self::new_(default_tls_connector())
+        Self::new_(default_tls_connector(&[]))
     }
 
     fn new_(tls: TlsConnector) -> Self {
         let mut http = HttpConnector::new();
         http.enforce_http(false);
-        HttpsConnector::from((http, tls))
+        let mut connector = HttpsConnector::from((http, tls));
+        connector.uses_default_tls = true;
+        connector
     }
 }
 
-#[cfg(not(feature = "rustls"))]
-fn default_tls_connector() -> TlsConnector {
-    native_tls::TlsConnector::new().map(|v| v.into())
+#[cfg(not(any(feature = "rustls", feature = "openssl")))]
+fn default_tls_connector(alpn_protocols: &[String]) -> TlsConnector {
+    let mut builder = native_tls::TlsConnector::builder();
+    if !alpn_protocols.is_empty() {
+        let protocols: Vec<&str> = alpn_protocols.iter().map(String::as_str).collect();
+        builder.request_alpns(&protocols);
+    }
+    builder
+        .build()
+        .map(|v| v.into())
         .unwrap_or_else(|e| panic!("native_tls::TlsConnector::new() failure: {}", e))
 }
 
+#[cfg(feature = "openssl")]
+fn default_tls_connector(alpn_protocols: &[String]) -> TlsConnector {
+    use openssl::ssl::{SslConnector, SslMethod};
+
+    let mut builder = SslConnector::builder(SslMethod::tls())
+        .unwrap_or_else(|e| panic!("openssl::ssl::SslConnector::builder() failure: {}", e));
+    if !alpn_protocols.is_empty() {
+        let wire_format = wire_format_alpn_protocols(alpn_protocols);
+        builder
+            .set_alpn_protos(&wire_format)
+            .unwrap_or_else(|e| panic!("SslConnectorBuilder::set_alpn_protos() failure: {}", e));
+    }
+    builder.build().into()
+}
+
+#[cfg(feature = "openssl")]
+fn wire_format_alpn_protocols(alpn_protocols: &[String]) -> Vec<u8> {
+    let mut wire_format = Vec::new();
+    for protocol in alpn_protocols {
+        wire_format.push(protocol.len() as u8);
+        wire_format.extend_from_slice(protocol.as_bytes());
+    }
+    wire_format
+}
+
 #[cfg(feature = "rustls")]
-fn default_tls_connector() -> TlsConnector {
+fn default_tls_connector(alpn_protocols: &[String]) -> TlsConnector {
     use tokio_rustls::rustls::{ClientConfig, RootCertStore};
 
     let mut trusted_certs = RootCertStore::empty();
@@ -91,11 +236,15 @@ fn default_tls_connector() -> TlsConnector {
         );
     }
 
-    let config = ClientConfig::builder()
+    let mut config = ClientConfig::builder()
         .with_safe_defaults()
         .with_root_certificates(trusted_certs)
         .with_no_client_auth();
 
+    if !alpn_protocols.is_empty() {
+        config.alpn_protocols = alpn_protocols.iter().map(|p| p.as_bytes().to_vec()).collect();
+    }
+
     TlsConnector::from(Arc::new(config))
 }
 
@@ -116,7 +265,71 @@ impl<T> HttpsConnector<T> {
     /// With connector constructor
     ///
     pub fn new_with_connector(http: T) -> Self {
-        HttpsConnector::from((http, default_tls_connector()))
+        let mut connector = HttpsConnector::from((http, default_tls_connector(&[])));
+        connector.uses_default_tls = true;
+        connector
+    }
+
+    /// Set the ALPN protocols to offer during the TLS handshake, in preference order.
+    ///
+    /// Offering `"h2"` lets the server negotiate HTTP/2 during the handshake, which is
+    /// what allows `hyper::Client` to pick HTTP/2 automatically instead of requiring
+    /// callers to hardcode the protocol version.
+    ///
+    /// # Panics
+    ///
+    /// This rebuilds the crate's default `TlsConnector`, so it panics if called on a
+    /// connector built from a custom `TlsConnector` via `HttpsConnector::from` — doing
+    /// so would otherwise silently discard that connector's root store, client certs,
+    /// or cipher configuration. Set ALPN directly on your `TlsConnector` before handing
+    /// it to `HttpsConnector::from` instead.
+    pub fn set_alpn_protocols(&mut self, protocols: &[&str]) {
+        assert!(
+            self.uses_default_tls,
+            "set_alpn_protocols only supports HttpsConnector's default TlsConnector; \
+             configure ALPN on your custom TlsConnector directly before passing it to \
+             HttpsConnector::from"
+        );
+        self.alpn_protocols = protocols.iter().map(|p| (*p).to_owned()).collect();
+        self.tls = default_tls_connector(&self.alpn_protocols);
+    }
+
+    /// Use a fixed DNS name for the TLS handshake (SNI) and certificate verification,
+    /// instead of deriving it from the `Uri` host passed to `call()`.
+    ///
+    /// This is useful when connecting by IP address or through a load-balancer
+    /// endpoint while still validating against a known certificate name, or when
+    /// routing many virtual hosts through one fixed endpoint. The real `Uri` is still
+    /// passed to the inner connector, so the TCP dial is unaffected.
+    pub fn set_server_name(&mut self, server_name: impl Into<String>) {
+        self.server_name = Some(server_name.into());
+    }
+
+    /// Tunnel connections through a forward HTTP proxy.
+    ///
+    /// See [`ProxyConfig`] for the tunneling behavior.
+    pub fn set_proxy(&mut self, proxy: ProxyConfig) {
+        self.proxy = Some(proxy);
+    }
+
+    /// Set `TCP_NODELAY` on the dialed socket, disabling Nagle's algorithm.
+    ///
+    /// This only takes effect when the inner connector's dialed connection is itself a
+    /// `tokio::net::TcpStream`, which is detected at runtime; this holds for hyper's
+    /// `HttpConnector`. It's a no-op for other transports, such as a Unix socket, a
+    /// mock connector, or a type that wraps a `TcpStream`.
+    pub fn set_nodelay(&mut self, enabled: bool) {
+        self.nodelay = enabled;
+    }
+
+    /// Enable TCP keepalive with the given idle time on the dialed socket.
+    ///
+    /// `None` (the default) leaves the socket's keepalive setting as the inner
+    /// connector left it; it does not disable keepalive.
+    ///
+    /// See [`HttpsConnector::set_nodelay`] for when this takes effect.
+    pub fn set_keepalive(&mut self, keepalive: Option<Duration>) {
+        self.keepalive = keepalive;
     }
 }
 
@@ -126,6 +339,12 @@ impl<T> From<(T, TlsConnector)> for HttpsConnector<T> {
             force_https: false,
             http: args.0,
             tls: args.1,
+            uses_default_tls: false,
+            alpn_protocols: Vec::new(),
+            server_name: None,
+            proxy: None,
+            nodelay: false,
+            keepalive: None,
         }
     }
 }
@@ -142,13 +361,13 @@ impl<T: fmt::Debug> fmt::Debug for HttpsConnector<T> {
 impl<T> Service<Uri> for HttpsConnector<T>
     where
         T: Service<Uri>,
-        T::Response: AsyncRead + AsyncWrite + Send + Unpin,
+        T::Response: AsyncRead + AsyncWrite + Send + Unpin + 'static,
         T::Future: Send + 'static,
         T::Error: Into<BoxError>,
 {
-    type Response = MaybeHttpsStream<T::Response>;
+    type Response = MaybeHttpsStream<PeekedStream<T::Response>>;
     type Error = BoxError;
-    type Future = HttpsConnecting<T::Response>;
+    type Future = HttpsConnecting<PeekedStream<T::Response>>;
 
     fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         match self.http.poll_ready(cx) {
@@ -165,15 +384,34 @@ impl<T> Service<Uri> for HttpsConnector<T>
             return err(ForceHttpsButUriNotHttps.into());
         }
 
-        let host = dst
-            .host()
-            .unwrap_or("")
-            .trim_matches(|c| c == '[' || c == ']')
-            .to_owned();
-        let connecting = self.http.call(dst);
+        let host = self.server_name.clone().unwrap_or_else(|| {
+            dst.host()
+                .unwrap_or("")
+                .trim_matches(|c| c == '[' || c == ']')
+                .to_owned()
+        });
+        let proxy = self.proxy.clone();
+        let dial_dst = match &proxy {
+            Some(proxy) => proxy.proxy.clone(),
+            None => dst.clone(),
+        };
+        let connecting = self.http.call(dial_dst);
         let tls = self.tls.clone();
+        let nodelay = self.nodelay;
+        let keepalive = self.keepalive;
         let fut = async move {
-            let tcp = connecting.await.map_err(Into::into)?;
+            let mut tcp = connecting.await.map_err(Into::into)?;
+
+            apply_tcp_options(&mut tcp, nodelay, keepalive)?;
+
+            let mut leading = Vec::new();
+            if is_https {
+                if let Some(proxy) = &proxy {
+                    leading = connect_through_proxy(&mut tcp, proxy, &dst).await?;
+                }
+            }
+            let tcp = PeekedStream::with_leading(tcp, leading);
+
             let maybe = if is_https {
                 #[cfg(feature = "rustls")]
                     let tls = {
@@ -229,3 +467,214 @@ impl fmt::Display for ForceHttpsButUriNotHttps {
 }
 
 impl std::error::Error for ForceHttpsButUriNotHttps {}
+
+#[derive(Debug)]
+struct ProxyConnectFailed(String);
+
+impl fmt::Display for ProxyConnectFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "proxy CONNECT request failed: {}", self.0)
+    }
+}
+
+impl std::error::Error for ProxyConnectFailed {}
+
+/// Dials a `CONNECT` tunnel to `target` through an already-connected proxy stream.
+///
+/// On success, returns any bytes read past the proxy's response that belong to the
+/// target's TLS handshake (the proxy's response and the handshake's first bytes can
+/// arrive in the same read); the caller must replay these to whatever reads `tcp` next,
+/// e.g. via [`crate::acceptor::PeekedStream`].
+async fn connect_through_proxy<T>(
+    tcp: &mut T,
+    proxy: &ProxyConfig,
+    target: &Uri,
+) -> Result<Vec<u8>, BoxError>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    // `Uri::authority()` never synthesizes a default port, so a URI like
+    // `https://example.com/` (the common case) would otherwise produce a bare
+    // `CONNECT example.com` with no port, which is not valid authority-form.
+    let host = target.host().unwrap_or_default();
+    let port = target.port_u16().unwrap_or(443);
+    let authority = format!("{}:{}", host, port);
+
+    let mut request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", authority);
+    if let Some(authorization) = &proxy.authorization {
+        request.push_str("Proxy-Authorization: ");
+        request.push_str(authorization);
+        request.push_str("\r\n");
+    }
+    request.push_str("\r\n");
+
+    tcp.write_all(request.as_bytes()).await?;
+    tcp.flush().await?;
+
+    // Cap how much of the proxy's response we're willing to buffer: a slow or
+    // malicious proxy that never sends a blank line would otherwise grow `response`
+    // without bound while we wait for one.
+    const MAX_RESPONSE_LEN: usize = 8 * 1024;
+
+    let mut response = Vec::with_capacity(512);
+    let mut chunk = [0u8; 512];
+    let header_end = loop {
+        let n = tcp.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(
+                ProxyConnectFailed("proxy closed the connection before responding".into()).into(),
+            );
+        }
+        response.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = response.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        if response.len() > MAX_RESPONSE_LEN {
+            return Err(ProxyConnectFailed(format!(
+                "proxy response headers exceeded {} bytes without completing",
+                MAX_RESPONSE_LEN
+            ))
+            .into());
+        }
+    };
+
+    // Whatever's left after the blank line belongs to the target's TLS handshake, not
+    // the proxy's response; keep it instead of silently dropping it.
+    let leftover = response.split_off(header_end);
+
+    let status_line = response
+        .split(|&b| b == b'\n')
+        .next()
+        .unwrap_or(&[])
+        .to_owned();
+    let status_line = String::from_utf8_lossy(&status_line).trim().to_owned();
+
+    if !status_line.contains(" 200") {
+        return Err(ProxyConnectFailed(status_line).into());
+    }
+
+    Ok(leftover)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// A minimal in-memory stream for testing `connect_through_proxy` without a real
+    /// TLS/network stack: `reads` supplies each `poll_read` call's bytes in turn, so a
+    /// test can control exactly how a response is split across reads, and writes are
+    /// recorded into `written` for inspection.
+    struct MockStream {
+        reads: VecDeque<Vec<u8>>,
+        written: Vec<u8>,
+    }
+
+    impl MockStream {
+        fn new(reads: Vec<Vec<u8>>) -> Self {
+            MockStream {
+                reads: reads.into(),
+                written: Vec::new(),
+            }
+        }
+    }
+
+    impl AsyncRead for MockStream {
+        unsafe fn prepare_uninitialized_buffer(&self, _buf: &mut [u8]) -> bool {
+            false
+        }
+
+        fn poll_read(
+            self: Pin<&mut Self>,
+            _cx: &mut Context,
+            buf: &mut [u8],
+        ) -> Poll<Result<usize, io::Error>> {
+            let this = self.get_mut();
+            match this.reads.pop_front() {
+                Some(chunk) => {
+                    let n = chunk.len();
+                    buf[..n].copy_from_slice(&chunk);
+                    Poll::Ready(Ok(n))
+                }
+                None => Poll::Ready(Ok(0)),
+            }
+        }
+    }
+
+    impl AsyncWrite for MockStream {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<Result<usize, io::Error>> {
+            self.get_mut().written.extend_from_slice(buf);
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn uri(s: &str) -> Uri {
+        s.parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn connect_through_proxy_replays_bytes_read_past_the_response() {
+        let mut tcp = MockStream::new(vec![
+            b"HTTP/1.1 200".to_vec(),
+            b" Connection established\r\n\r\nleftover".to_vec(),
+        ]);
+        let proxy = ProxyConfig::new(uri("http://proxy.example:8080"));
+        let leftover = connect_through_proxy(&mut tcp, &proxy, &uri("https://example.com"))
+            .await
+            .unwrap();
+        assert_eq!(leftover, b"leftover");
+    }
+
+    #[tokio::test]
+    async fn connect_through_proxy_rejects_non_200_status() {
+        let mut tcp = MockStream::new(vec![
+            b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n".to_vec()
+        ]);
+        let proxy = ProxyConfig::new(uri("http://proxy.example:8080"));
+        let err = connect_through_proxy(&mut tcp, &proxy, &uri("https://example.com"))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("407"));
+    }
+
+    #[tokio::test]
+    async fn connect_through_proxy_enforces_response_size_cap() {
+        let mut remaining = 9 * 1024;
+        let mut reads = Vec::new();
+        while remaining > 0 {
+            let n = remaining.min(512);
+            reads.push(vec![b'x'; n]);
+            remaining -= n;
+        }
+        let mut tcp = MockStream::new(reads);
+        let proxy = ProxyConfig::new(uri("http://proxy.example:8080"));
+        let err = connect_through_proxy(&mut tcp, &proxy, &uri("https://example.com"))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("exceeded"));
+    }
+
+    #[tokio::test]
+    async fn connect_through_proxy_authority_gets_a_default_port() {
+        let mut tcp = MockStream::new(vec![b"HTTP/1.1 200 OK\r\n\r\n".to_vec()]);
+        let proxy = ProxyConfig::new(uri("http://proxy.example:8080"));
+        connect_through_proxy(&mut tcp, &proxy, &uri("https://example.com"))
+            .await
+            .unwrap();
+        let request = String::from_utf8(tcp.written).unwrap();
+        assert!(request.starts_with("CONNECT example.com:443 HTTP/1.1\r\n"));
+        assert!(request.contains("Host: example.com:443\r\n"));
+    }
+}
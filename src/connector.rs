@@ -0,0 +1,79 @@
+//! A thin wrapper around the synchronous `native_tls`/`openssl` connector types that
+//! drives their handshake asynchronously via the `SyncStream`/`Handshaking` bridge in
+//! `stream.rs`.
+//!
+//! Under the `rustls` feature this module isn't used at all; `tokio_rustls::TlsConnector`
+//! is already async and is used directly instead.
+use tokio_io::{AsyncRead, AsyncWrite};
+
+use crate::stream::{Handshaking, SyncStream, TlsStream};
+
+#[cfg(not(feature = "openssl"))]
+#[derive(Clone)]
+pub(crate) struct TlsConnector {
+    connector: native_tls::TlsConnector,
+}
+
+#[cfg(not(feature = "openssl"))]
+impl TlsConnector {
+    pub(crate) async fn connect<T>(
+        &self,
+        host: &str,
+        stream: T,
+    ) -> Result<TlsStream<T>, native_tls::Error>
+    where
+        T: AsyncRead + AsyncWrite + Unpin,
+    {
+        let connector = self.connector.clone();
+        let stream = SyncStream { inner: stream };
+        Handshaking {
+            inner: Some(connector.connect(host, stream)),
+        }
+        .await
+        .map(TlsStream::new)
+    }
+}
+
+#[cfg(not(feature = "openssl"))]
+impl From<native_tls::TlsConnector> for TlsConnector {
+    fn from(connector: native_tls::TlsConnector) -> Self {
+        TlsConnector { connector }
+    }
+}
+
+#[cfg(feature = "openssl")]
+#[derive(Clone)]
+pub(crate) struct TlsConnector {
+    connector: openssl::ssl::SslConnector,
+}
+
+#[cfg(feature = "openssl")]
+impl TlsConnector {
+    pub(crate) async fn connect<T>(
+        &self,
+        host: &str,
+        stream: T,
+    ) -> Result<TlsStream<T>, openssl::ssl::Error>
+    where
+        T: AsyncRead + AsyncWrite + Unpin,
+    {
+        let config = self
+            .connector
+            .configure()
+            .map_err(openssl::ssl::Error::from)?
+            .verify_hostname(true);
+        let stream = SyncStream { inner: stream };
+        Handshaking {
+            inner: Some(config.connect(host, stream)),
+        }
+        .await
+        .map(TlsStream::new)
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl From<openssl::ssl::SslConnector> for TlsConnector {
+    fn from(connector: openssl::ssl::SslConnector) -> Self {
+        TlsConnector { connector }
+    }
+}
@@ -26,8 +26,21 @@
 #![deny(missing_docs)]
 #![deny(missing_debug_implementations)]
 
-pub use client::{Error, HttpsConnecting, HttpsConnector};
-pub use stream::{MaybeHttpsStream, TlsStream};
+pub use acceptor::{HttpsAcceptor, PeekedStream, TlsAcceptor};
+pub use client::{Error, HttpsConnecting, HttpsConnector, ProxyConfig};
+pub use stream::{Certificate, MaybeHttpsStream};
+#[cfg(not(feature = "rustls"))]
+pub use stream::TlsStream;
+#[cfg(feature = "rustls")]
+pub use tokio_rustls::TlsStream;
 
+#[cfg(feature = "rustls")]
+pub(crate) use tokio_rustls::TlsConnector;
+#[cfg(not(feature = "rustls"))]
+pub(crate) use connector::TlsConnector;
+
+mod acceptor;
 mod client;
+#[cfg(not(feature = "rustls"))]
+mod connector;
 mod stream;
@@ -3,19 +3,31 @@ use std::io::{self, Read, Write};
 use std::pin::Pin;
 use std::task::{Context, Poll, Waker};
 
+#[cfg(all(not(feature = "rustls"), not(feature = "openssl")))]
 use native_tls;
+#[cfg(all(not(feature = "rustls"), not(feature = "openssl")))]
 use native_tls::HandshakeError;
+#[cfg(not(feature = "rustls"))]
 use scoped_tls::scoped_thread_local;
+#[cfg(not(feature = "rustls"))]
 use std::future::Future;
 use tokio_io::{AsyncRead, AsyncWrite};
 
+use hyper::client::connect::{Connected, Connection};
+
+#[cfg(feature = "rustls")]
+use tokio_rustls::TlsStream;
+
+#[cfg(not(feature = "rustls"))]
 scoped_thread_local!(static WAKER: Waker);
 
+#[cfg(not(feature = "rustls"))]
 #[derive(Debug)]
 pub struct SyncStream<S> {
     pub(crate) inner: S,
 }
 
+#[cfg(not(feature = "rustls"))]
 impl<S: Unpin> SyncStream<S> {
     fn with_context<F, R>(&mut self, f: F) -> Result<R, io::Error>
     where
@@ -32,6 +44,7 @@ impl<S: Unpin> SyncStream<S> {
     }
 }
 
+#[cfg(not(feature = "rustls"))]
 impl<S: AsyncWrite + Unpin> Write for SyncStream<S> {
     fn write(&mut self, buf: &[u8]) -> Result<usize, io::Error> {
         self.with_context(|cx, s| match s.poll_write(cx, buf) {
@@ -48,6 +61,7 @@ impl<S: AsyncWrite + Unpin> Write for SyncStream<S> {
     }
 }
 
+#[cfg(not(feature = "rustls"))]
 impl<S: AsyncRead + Unpin> Read for SyncStream<S> {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, io::Error> {
         self.with_context(|cx, s| match s.poll_read(cx, buf) {
@@ -66,10 +80,17 @@ pub enum MaybeHttpsStream<T> {
 }
 
 /// A stream protected with TLS.
+#[cfg(all(not(feature = "rustls"), not(feature = "openssl")))]
 pub struct TlsStream<T> {
     inner: native_tls::TlsStream<SyncStream<T>>,
 }
 
+/// A stream protected with TLS.
+#[cfg(feature = "openssl")]
+pub struct TlsStream<T> {
+    inner: openssl::ssl::SslStream<SyncStream<T>>,
+}
+
 // ===== impl MaybeHttpsStream =====
 
 impl<T: fmt::Debug> fmt::Debug for MaybeHttpsStream<T> {
@@ -81,12 +102,20 @@ impl<T: fmt::Debug> fmt::Debug for MaybeHttpsStream<T> {
     }
 }
 
+#[cfg(all(not(feature = "rustls"), not(feature = "openssl")))]
 impl<T> From<native_tls::TlsStream<SyncStream<T>>> for MaybeHttpsStream<T> {
     fn from(inner: native_tls::TlsStream<SyncStream<T>>) -> Self {
         MaybeHttpsStream::Https(TlsStream::from(inner))
     }
 }
 
+#[cfg(feature = "openssl")]
+impl<T> From<openssl::ssl::SslStream<SyncStream<T>>> for MaybeHttpsStream<T> {
+    fn from(inner: openssl::ssl::SslStream<SyncStream<T>>) -> Self {
+        MaybeHttpsStream::Https(TlsStream::from(inner))
+    }
+}
+
 impl<T> From<T> for MaybeHttpsStream<T> {
     fn from(inner: T) -> Self {
         MaybeHttpsStream::Http(inner)
@@ -147,8 +176,153 @@ impl<T: AsyncWrite + AsyncRead + Unpin> AsyncWrite for MaybeHttpsStream<T> {
     }
 }
 
+/// Lets hyper's connection pool know whether the negotiated ALPN protocol was
+/// `h2`, so `Client` can upgrade the connection to HTTP/2 automatically.
+impl<T: Connection + AsyncRead + AsyncWrite + Unpin> Connection for MaybeHttpsStream<T> {
+    fn connected(&self) -> Connected {
+        match self {
+            MaybeHttpsStream::Http(s) => s.connected(),
+            MaybeHttpsStream::Https(s) => {
+                #[cfg(not(feature = "rustls"))]
+                {
+                    let connected = s.connected_inner();
+                    match s.negotiated_alpn() {
+                        Some(ref p) if p == b"h2" => connected.negotiated_h2(),
+                        _ => connected,
+                    }
+                }
+
+                #[cfg(feature = "rustls")]
+                {
+                    let (io, session) = s.get_ref();
+                    let connected = io.connected();
+                    match session.alpn_protocol() {
+                        Some(b"h2") => connected.negotiated_h2(),
+                        _ => connected,
+                    }
+                }
+            }
+        }
+    }
+}
+
+// ===== certificate / channel-binding introspection =====
+
+/// The peer certificate presented during a completed TLS handshake.
+///
+/// This hides whichever backend (`native-tls`, `rustls`, or `openssl`) is active behind
+/// a single public type, so callers doing certificate pinning or logging don't need to
+/// depend on a particular backend's certificate type.
+pub struct Certificate(CertificateImpl);
+
+enum CertificateImpl {
+    #[cfg(all(not(feature = "rustls"), not(feature = "openssl")))]
+    NativeTls(native_tls::Certificate),
+    #[cfg(feature = "rustls")]
+    Rustls(tokio_rustls::rustls::Certificate),
+    #[cfg(feature = "openssl")]
+    Openssl(openssl::x509::X509),
+}
+
+impl Certificate {
+    /// Returns the DER-encoded bytes of the certificate.
+    pub fn to_der(&self) -> io::Result<Vec<u8>> {
+        match &self.0 {
+            #[cfg(all(not(feature = "rustls"), not(feature = "openssl")))]
+            CertificateImpl::NativeTls(cert) => cert
+                .to_der()
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e)),
+            #[cfg(feature = "rustls")]
+            CertificateImpl::Rustls(cert) => Ok(cert.0.clone()),
+            #[cfg(feature = "openssl")]
+            CertificateImpl::Openssl(cert) => cert
+                .to_der()
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e)),
+        }
+    }
+}
+
+impl fmt::Debug for Certificate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("Certificate")
+    }
+}
+
+/// Computes the `tls-server-end-point` channel binding data for a DER-encoded
+/// certificate, as defined in RFC 5929 §4.1.
+///
+/// RFC 5929 calls for hashing with the certificate's own signature algorithm, falling
+/// back to SHA-256 when that algorithm is MD5 or SHA-1 (or unknown). This always uses
+/// SHA-256, so the result matches the RFC only for certificates signed with MD5, SHA-1
+/// or SHA-256; for a SHA-384 or SHA-512 signature it differs from the RFC's value.
+fn tls_server_end_point_hash(der: &[u8]) -> Vec<u8> {
+    use sha2::{Digest, Sha256};
+    Sha256::digest(der).to_vec()
+}
+
+impl<T> MaybeHttpsStream<T> {
+    /// Returns the peer certificate presented during the TLS handshake.
+    ///
+    /// Returns `None` for plaintext (`Http`) connections.
+    pub fn peer_certificate(&self) -> Option<Certificate> {
+        match self {
+            MaybeHttpsStream::Http(_) => None,
+            MaybeHttpsStream::Https(s) => {
+                #[cfg(not(feature = "rustls"))]
+                {
+                    s.peer_certificate()
+                }
+
+                #[cfg(feature = "rustls")]
+                {
+                    let (_, session) = s.get_ref();
+                    session
+                        .peer_certificates()
+                        .and_then(|certs| certs.first())
+                        .cloned()
+                        .map(|cert| Certificate(CertificateImpl::Rustls(cert)))
+                }
+            }
+        }
+    }
+
+    /// Returns the ALPN protocol negotiated during the TLS handshake, if any.
+    ///
+    /// Returns `None` for plaintext (`Http`) connections.
+    pub fn negotiated_alpn(&self) -> Option<Vec<u8>> {
+        match self {
+            MaybeHttpsStream::Http(_) => None,
+            MaybeHttpsStream::Https(s) => {
+                #[cfg(not(feature = "rustls"))]
+                {
+                    s.negotiated_alpn()
+                }
+
+                #[cfg(feature = "rustls")]
+                {
+                    let (_, session) = s.get_ref();
+                    session.alpn_protocol().map(|p| p.to_vec())
+                }
+            }
+        }
+    }
+
+    /// Returns the `tls-server-end-point` channel binding hash for this connection
+    /// (RFC 5929 §4.1), for use with channel-bound auth mechanisms like SCRAM-PLUS.
+    ///
+    /// The certificate is always hashed with SHA-256, whatever its signature algorithm.
+    ///
+    /// Returns `None` for plaintext connections or if the peer presented no certificate.
+    pub fn tls_server_end_point(&self) -> Option<Vec<u8>> {
+        self.peer_certificate()
+            .and_then(|cert| cert.to_der().ok())
+            .map(|der| tls_server_end_point_hash(&der))
+    }
+}
+
 // ===== impl TlsStream =====
 
+#[cfg(all(not(feature = "rustls"), not(feature = "openssl")))]
 impl<T> TlsStream<T> {
     pub(crate) fn new(inner: native_tls::TlsStream<SyncStream<T>>) -> Self {
         TlsStream { inner }
@@ -165,20 +339,100 @@ impl<T> TlsStream<T> {
     pub fn get_mut(&mut self) -> &mut native_tls::TlsStream<SyncStream<T>> {
         &mut self.inner
     }
+
+    /// Returns the peer certificate presented during the TLS handshake.
+    pub fn peer_certificate(&self) -> Option<Certificate> {
+        self.inner
+            .peer_certificate()
+            .ok()
+            .flatten()
+            .map(|cert| Certificate(CertificateImpl::NativeTls(cert)))
+    }
+
+    /// Returns the ALPN protocol negotiated during the TLS handshake, if any.
+    pub fn negotiated_alpn(&self) -> Option<Vec<u8>> {
+        self.inner.negotiated_alpn().ok().flatten()
+    }
 }
 
+#[cfg(all(not(feature = "rustls"), not(feature = "openssl")))]
+impl<T: Connection> TlsStream<T> {
+    pub(crate) fn connected_inner(&self) -> Connected {
+        self.inner.get_ref().inner.connected()
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl<T> TlsStream<T> {
+    pub(crate) fn new(inner: openssl::ssl::SslStream<SyncStream<T>>) -> Self {
+        TlsStream { inner }
+    }
+
+    /// Get access to the internal `openssl::ssl::SslStream` stream which also
+    /// transitively allows access to `T`.
+    pub fn get_ref(&self) -> &openssl::ssl::SslStream<SyncStream<T>> {
+        &self.inner
+    }
+
+    /// Get mutable access to the internal `openssl::ssl::SslStream` stream which
+    /// also transitively allows mutable access to `T`.
+    pub fn get_mut(&mut self) -> &mut openssl::ssl::SslStream<SyncStream<T>> {
+        &mut self.inner
+    }
+
+    /// Returns the peer certificate presented during the TLS handshake.
+    pub fn peer_certificate(&self) -> Option<Certificate> {
+        self.inner
+            .ssl()
+            .peer_certificate()
+            .map(|cert| Certificate(CertificateImpl::Openssl(cert)))
+    }
+
+    /// Returns the ALPN protocol negotiated during the TLS handshake, if any.
+    pub fn negotiated_alpn(&self) -> Option<Vec<u8>> {
+        self.inner
+            .ssl()
+            .selected_alpn_protocol()
+            .map(|p| p.to_vec())
+    }
+}
+
+#[cfg(feature = "openssl")]
+impl<T: Connection> TlsStream<T> {
+    pub(crate) fn connected_inner(&self) -> Connected {
+        self.inner.get_ref().inner.connected()
+    }
+}
+
+#[cfg(all(not(feature = "rustls"), not(feature = "openssl")))]
 impl<T: fmt::Debug> fmt::Debug for TlsStream<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         fmt::Debug::fmt(&self.inner, f)
     }
 }
 
+#[cfg(feature = "openssl")]
+impl<T: fmt::Debug> fmt::Debug for TlsStream<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.inner.get_ref(), f)
+    }
+}
+
+#[cfg(all(not(feature = "rustls"), not(feature = "openssl")))]
 impl<T> From<native_tls::TlsStream<SyncStream<T>>> for TlsStream<T> {
     fn from(stream: native_tls::TlsStream<SyncStream<T>>) -> Self {
         TlsStream { inner: stream }
     }
 }
 
+#[cfg(feature = "openssl")]
+impl<T> From<openssl::ssl::SslStream<SyncStream<T>>> for TlsStream<T> {
+    fn from(stream: openssl::ssl::SslStream<SyncStream<T>>) -> Self {
+        TlsStream { inner: stream }
+    }
+}
+
+#[cfg(not(feature = "rustls"))]
 impl<T: AsyncWrite + AsyncRead + Unpin> AsyncRead for TlsStream<T> {
     fn poll_read(
         self: Pin<&mut Self>,
@@ -193,6 +447,7 @@ impl<T: AsyncWrite + AsyncRead + Unpin> AsyncRead for TlsStream<T> {
     }
 }
 
+#[cfg(not(feature = "rustls"))]
 impl<T: AsyncWrite + AsyncRead + Unpin> AsyncWrite for TlsStream<T> {
     fn poll_write(
         self: Pin<&mut Self>,
@@ -223,10 +478,12 @@ impl<T: AsyncWrite + AsyncRead + Unpin> AsyncWrite for TlsStream<T> {
     }
 }
 
+#[cfg(all(not(feature = "rustls"), not(feature = "openssl")))]
 pub struct Handshaking<T> {
     pub(crate) inner: Option<Result<native_tls::TlsStream<T>, HandshakeError<T>>>,
 }
 
+#[cfg(all(not(feature = "rustls"), not(feature = "openssl")))]
 impl<T: io::Read + io::Write + Unpin> Future for Handshaking<T> {
     type Output = Result<native_tls::TlsStream<T>, native_tls::Error>;
 
@@ -247,3 +504,36 @@ impl<T: io::Read + io::Write + Unpin> Future for Handshaking<T> {
         })
     }
 }
+
+/// Drives `openssl`'s synchronous handshake state machine from an async context, the
+/// same way [`Handshaking`] does for `native_tls`.
+#[cfg(feature = "openssl")]
+pub struct Handshaking<T> {
+    pub(crate) inner: Option<Result<openssl::ssl::SslStream<T>, openssl::ssl::HandshakeError<T>>>,
+}
+
+#[cfg(feature = "openssl")]
+impl<T: io::Read + io::Write + Unpin> Future for Handshaking<T> {
+    type Output = Result<openssl::ssl::SslStream<T>, openssl::ssl::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        use openssl::ssl::HandshakeError;
+
+        let this = self.get_mut();
+        let inner = this.inner.take().expect("polled after ready");
+        WAKER.set(cx.waker(), || match inner {
+            Ok(stream) => Poll::Ready(Ok(stream)),
+            Err(HandshakeError::WouldBlock(mid)) => match mid.handshake() {
+                Ok(stream) => Poll::Ready(Ok(stream)),
+                Err(HandshakeError::Failure(mid)) => Poll::Ready(Err(mid.into_error())),
+                Err(HandshakeError::WouldBlock(mid)) => {
+                    this.inner = Some(Err(HandshakeError::WouldBlock(mid)));
+                    Poll::Pending
+                }
+                Err(HandshakeError::SetupFailure(e)) => Poll::Ready(Err(e.into())),
+            },
+            Err(HandshakeError::Failure(mid)) => Poll::Ready(Err(mid.into_error())),
+            Err(HandshakeError::SetupFailure(e)) => Poll::Ready(Err(e.into())),
+        })
+    }
+}